@@ -0,0 +1,8 @@
+use crate::map_model::RoadID;
+
+/// A cell is a group of interior roads that're mutually reachable without
+/// crossing a modal filter -- the connected components the LTN is carved
+/// into once filters are applied.
+pub struct Cell {
+    pub roads: Vec<RoadID>,
+}
@@ -0,0 +1,52 @@
+use std::collections::BTreeMap;
+
+use geo::Coord;
+
+use crate::map_model::FilterKind;
+
+/// Free-form key/value tags, following OSM conventions.
+pub type Tags = BTreeMap<String, String>;
+
+/// Maps an OSM `barrier` tag value to the closest `FilterKind` we model.
+/// Barriers we don't recognize as through-traffic blockers are ignored.
+pub fn barrier_to_filter_kind(barrier: &str) -> Option<FilterKind> {
+    match barrier {
+        // A bollard or block physically blocks motor traffic but still lets
+        // people walk or cycle through -- `NoEntry` would model a sign, not
+        // a physical barrier.
+        "bollard" | "block" => Some(FilterKind::WalkCycleOnly),
+        "gate" | "lift_gate" => Some(FilterKind::BusGate),
+        _ => None,
+    }
+}
+
+/// Encodes a sequence of WGS84 (lng, lat) points with the Google encoded
+/// polyline algorithm: 5 decimal places of precision, delta-encoded,
+/// zig-zag + base64-ish varints. See
+/// <https://developers.google.com/maps/documentation/utilities/polylinealgorithm>.
+pub fn encode_polyline(points: &[Coord]) -> String {
+    let mut out = String::new();
+    let mut prev_lat = 0i64;
+    let mut prev_lng = 0i64;
+    for pt in points {
+        let lat = (pt.y * 1e5).round() as i64;
+        let lng = (pt.x * 1e5).round() as i64;
+        encode_value(lat - prev_lat, &mut out);
+        encode_value(lng - prev_lng, &mut out);
+        prev_lat = lat;
+        prev_lng = lng;
+    }
+    out
+}
+
+fn encode_value(value: i64, out: &mut String) {
+    let mut v = value << 1;
+    if value < 0 {
+        v = !v;
+    }
+    while v >= 0x20 {
+        out.push((((v & 0x1f) | 0x20) as u8 + 63) as char);
+        v >>= 5;
+    }
+    out.push((v as u8 + 63) as char);
+}
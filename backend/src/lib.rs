@@ -28,6 +28,7 @@ mod render_cells;
 mod route;
 mod scrape;
 mod shortcuts;
+mod simplify;
 
 static START: Once = Once::new();
 
@@ -198,6 +199,18 @@ impl LTN {
         .map_err(err_to_js)?)
     }
 
+    /// Returns a GeoJSON FeatureCollection coloring every interior road by how
+    /// many origin-destination pairs route through it, before and after the
+    /// current modal filters. See `shortcuts::score_all_shortcuts`.
+    #[wasm_bindgen(js_name = scoreAllShortcuts)]
+    pub fn score_all_shortcuts(&self) -> Result<String, JsValue> {
+        Ok(serde_json::to_string(&shortcuts::score_all_shortcuts(
+            &self.map,
+            self.neighbourhood.as_ref().unwrap(),
+        ))
+        .map_err(err_to_js)?)
+    }
+
     /// GJ with modal filters and optionally the neighbourhood boundary
     #[wasm_bindgen(js_name = toSavefile)]
     pub fn to_savefile(&self) -> Result<String, JsValue> {
@@ -223,12 +236,40 @@ impl LTN {
         }
     }
 
-    /// Returns GJ with two LineStrings, before and after
+    /// Unions another planner's savefile (exported via `toSavefile`) into this
+    /// one, resolving any conflicting edits to the same road deterministically,
+    /// and returns the new combined heads.
+    #[wasm_bindgen(js_name = mergeSavefile)]
+    pub fn merge_savefile(&mut self, input: JsValue) -> Result<String, JsValue> {
+        let gj: FeatureCollection = serde_wasm_bindgen::from_value(input)?;
+        self.map.merge_savefile(gj).map_err(err_to_js)
+    }
+
+    /// An opaque token summarizing the current edit history, for checking
+    /// whether two planners' savefiles have diverged.
+    pub fn commit(&self) -> String {
+        self.map.commit()
+    }
+
+    /// Returns before/after distance, time, and geometry (as GeoJSON or an
+    /// encoded polyline, per `as_polyline`) for the given travel `profile`
+    /// ("car", "bicycle", or "foot").
     #[wasm_bindgen(js_name = compareRoute)]
-    pub fn compare_route(&self, x1: f64, y1: f64, x2: f64, y2: f64) -> Result<String, JsValue> {
+    pub fn compare_route(
+        &self,
+        x1: f64,
+        y1: f64,
+        x2: f64,
+        y2: f64,
+        profile: String,
+        as_polyline: bool,
+    ) -> Result<String, JsValue> {
         let pt1 = self.map.mercator.pt_to_mercator(Coord { x: x1, y: y1 });
         let pt2 = self.map.mercator.pt_to_mercator(Coord { x: x2, y: y2 });
-        Ok(serde_json::to_string(&self.map.compare_route(pt1, pt2)).map_err(err_to_js)?)
+        let profile = route::Profile::from_string(&profile)
+            .ok_or_else(|| err_to_js(format!("unknown profile {profile}")))?;
+        Ok(serde_json::to_string(&self.map.compare_route(pt1, pt2, profile, as_polyline))
+            .map_err(err_to_js)?)
     }
 }
 
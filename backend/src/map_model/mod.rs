@@ -0,0 +1,531 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use geo::{Coord, LineString, Polygon};
+use geojson::{Feature, Geometry};
+use serde::{Deserialize, Serialize};
+use utils::Mercator;
+
+use crate::common::Tags;
+use crate::neighbourhood::Neighbourhood;
+use crate::route::Router;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct RoadID(pub usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct IntersectionID(pub usize);
+
+pub struct Road {
+    pub id: RoadID,
+    pub src_i: IntersectionID,
+    pub dst_i: IntersectionID,
+    pub linestring: LineString,
+    pub tags: Tags,
+    /// Can motor traffic drive from `src_i` to `dst_i`? False for a one-way
+    /// street running the other direction, or for `access`/`motor_vehicle=no`.
+    pub access_forward: bool,
+    /// Can motor traffic drive from `dst_i` to `src_i`?
+    pub access_backward: bool,
+}
+
+impl Road {
+    pub fn to_gj(&self, mercator: &Mercator) -> Feature {
+        let mut f = Feature::from(Geometry::from(&mercator.to_wgs84(&self.linestring)));
+        f.set_property("id", self.id.0);
+        f.set_property("name", self.tags.get("name").cloned());
+        f.set_property("access_forward", self.access_forward);
+        f.set_property("access_backward", self.access_backward);
+        f
+    }
+
+    pub fn length_meters(&self) -> f64 {
+        use geo::EuclideanLength;
+        self.linestring.euclidean_length()
+    }
+}
+
+pub struct Intersection {
+    pub id: IntersectionID,
+    pub point: Coord,
+    pub roads: Vec<RoadID>,
+}
+
+/// How a modal filter physically stops motor traffic.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FilterKind {
+    WalkCycleOnly,
+    NoEntry,
+    BusGate,
+    SchoolStreet,
+}
+
+impl FilterKind {
+    pub fn from_string(x: &str) -> Option<Self> {
+        match x {
+            "walk_cycle_only" => Some(Self::WalkCycleOnly),
+            "no_entry" => Some(Self::NoEntry),
+            "bus_gate" => Some(Self::BusGate),
+            "school_street" => Some(Self::SchoolStreet),
+            _ => None,
+        }
+    }
+
+    pub fn to_string(self) -> &'static str {
+        match self {
+            Self::WalkCycleOnly => "walk_cycle_only",
+            Self::NoEntry => "no_entry",
+            Self::BusGate => "bus_gate",
+            Self::SchoolStreet => "school_street",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ModalFilter {
+    pub kind: FilterKind,
+    /// Where along the road's linestring, from 0 to 1
+    pub percent_along: f64,
+}
+
+/// A banned turn imported from an OSM `type=restriction` relation: driving
+/// from `from` onto `to`, via `via`, isn't allowed.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Restriction {
+    pub from: RoadID,
+    pub via: IntersectionID,
+    pub to: RoadID,
+}
+
+/// One change to a road's modal filter, as a CRDT op. Every planner editing a
+/// neighbourhood has their own `actor` id and a counter that only increases,
+/// so `(counter, actor)` gives a total order over concurrent edits to the
+/// same road.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum OpKind {
+    AddFilter(FilterKind),
+    DeleteFilter,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Op {
+    pub actor: String,
+    pub counter: u64,
+    pub road: RoadID,
+    pub kind: OpKind,
+}
+
+pub struct MapModel {
+    pub roads: Vec<Road>,
+    pub intersections: Vec<Intersection>,
+    pub mercator: Mercator,
+    pub boundary_polygon: Polygon,
+    pub router: Router,
+
+    /// Proposed filters, from the mergeable edit log.
+    pub modal_filters: HashMap<RoadID, ModalFilter>,
+    /// Filters that physically already exist in OSM. Read-only -- planners
+    /// edit `modal_filters`, not these.
+    pub existing_filters: HashMap<RoadID, ModalFilter>,
+    pub turn_restrictions: Vec<Restriction>,
+
+    // Roads whose winning op log entry is a `DeleteFilter`, derived fresh in
+    // `rebuild_modal_filters`. A road can have a `DeleteFilter` win without
+    // ever appearing in `modal_filters` -- that's how a planner removes a
+    // physically-imported filter from `existing_filters`, which the op log
+    // never touches directly.
+    deleted_filters: HashSet<RoadID>,
+
+    actor: String,
+    next_counter: u64,
+    op_log: Vec<Op>,
+    // Ops this actor has undone, available to redo. Purely a local UI nicety;
+    // it's not part of the CRDT state shared with other planners.
+    undo_buffer: Vec<Op>,
+}
+
+impl MapModel {
+    pub fn new(input_bytes: &[u8]) -> Result<Self> {
+        let scraped = crate::scrape::scrape(input_bytes)?;
+        // Collapse degree-2 intersections that're artifacts of how OSM splits
+        // ways, not real junctions, before building the router or handing
+        // anything to neighbourhoods/cells/shortcuts.
+        let (roads, intersections, existing_filters, turn_restrictions) = crate::simplify::simplify(
+            scraped.roads,
+            scraped.intersections,
+            scraped.existing_filters,
+            scraped.turn_restrictions,
+        );
+        let router = Router::new(&roads, &intersections, &turn_restrictions);
+        Ok(Self {
+            roads,
+            intersections,
+            mercator: scraped.mercator,
+            boundary_polygon: scraped.boundary_polygon,
+            router,
+            modal_filters: HashMap::new(),
+            existing_filters,
+            turn_restrictions,
+            deleted_filters: HashSet::new(),
+            actor: new_actor_id(),
+            next_counter: 0,
+            op_log: Vec::new(),
+            undo_buffer: Vec::new(),
+        })
+    }
+
+    /// The union of imported and proposed filters: what the network actually
+    /// looks like after every currently-proposed change is applied. Proposed
+    /// filters win if a road somehow has both, and a proposed deletion (even
+    /// of a road with no proposed filter of its own) removes an imported one.
+    pub fn effective_filters(&self) -> HashMap<RoadID, ModalFilter> {
+        let mut out: HashMap<RoadID, ModalFilter> = self
+            .existing_filters
+            .iter()
+            .filter(|(r, _)| !self.deleted_filters.contains(r))
+            .map(|(r, f)| (*r, f.clone()))
+            .collect();
+        out.extend(
+            self.modal_filters
+                .iter()
+                .map(|(r, f)| (*r, f.clone())),
+        );
+        out
+    }
+
+    pub fn invert_boundary(&self) -> Polygon {
+        // The whole world in WGS84, with the boundary polygon cut out as a
+        // hole -- used to darken everything outside of the imported area.
+        Polygon::new(
+            LineString::from(vec![
+                (180.0, 90.0),
+                (-180.0, 90.0),
+                (-180.0, -90.0),
+                (180.0, -90.0),
+                (180.0, 90.0),
+            ]),
+            vec![self.mercator.to_wgs84(&self.boundary_polygon).exterior().clone()],
+        )
+    }
+
+    pub fn get_r(&self, r: RoadID) -> &Road {
+        &self.roads[r.0]
+    }
+
+    pub fn get_i(&self, i: IntersectionID) -> &Intersection {
+        &self.intersections[i.0]
+    }
+
+    pub fn add_modal_filter(&mut self, pt: Coord, interior_roads: &[RoadID], kind: FilterKind) {
+        if let Some(r) = self.closest_interior_road(pt, interior_roads) {
+            self.record_op(r, OpKind::AddFilter(kind));
+        }
+    }
+
+    pub fn add_many_modal_filters(
+        &mut self,
+        linestring: LineString,
+        interior_roads: &[RoadID],
+        kind: FilterKind,
+    ) {
+        use geo::algorithm::line_intersection::LineIntersection;
+        use geo::LinesIter;
+
+        for r in interior_roads {
+            let road = self.get_r(*r);
+            let mut hit = false;
+            for split_line in linestring.lines_iter() {
+                for road_line in road.linestring.lines_iter() {
+                    if matches!(
+                        geo::algorithm::line_intersection::line_intersection(split_line, road_line),
+                        Some(LineIntersection::SinglePoint { .. })
+                    ) {
+                        hit = true;
+                    }
+                }
+            }
+            if hit {
+                self.record_op(*r, OpKind::AddFilter(kind));
+            }
+        }
+    }
+
+    fn closest_interior_road(&self, pt: Coord, interior_roads: &[RoadID]) -> Option<RoadID> {
+        use geo::EuclideanDistance;
+
+        interior_roads
+            .iter()
+            .copied()
+            .min_by(|a, b| {
+                let da = self.get_r(*a).linestring.euclidean_distance(&pt);
+                let db = self.get_r(*b).linestring.euclidean_distance(&pt);
+                da.partial_cmp(&db).unwrap()
+            })
+    }
+
+    pub fn delete_modal_filter(&mut self, r: RoadID) {
+        // A road can have a filter to remove either because a planner
+        // proposed one, or because one's physically imported from OSM --
+        // either way, there's something here for a `DeleteFilter` to undo.
+        if self.modal_filters.contains_key(&r) || self.existing_filters.contains_key(&r) {
+            self.record_op(r, OpKind::DeleteFilter);
+        }
+    }
+
+    /// Appends a new op by this actor to the log and rebuilds derived state.
+    fn record_op(&mut self, road: RoadID, kind: OpKind) {
+        let op = Op {
+            actor: self.actor.clone(),
+            counter: self.next_counter,
+            road,
+            kind,
+        };
+        self.next_counter += 1;
+        self.op_log.push(op);
+        self.undo_buffer.clear();
+        self.rebuild_modal_filters();
+    }
+
+    /// Undoes this actor's most recent op. (Ops from other actors, merged in
+    /// from elsewhere, aren't ours to undo.)
+    pub fn undo(&mut self) {
+        if let Some(idx) = self.op_log.iter().rposition(|o| o.actor == self.actor) {
+            let op = self.op_log.remove(idx);
+            self.undo_buffer.push(op);
+            self.rebuild_modal_filters();
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(op) = self.undo_buffer.pop() {
+            self.op_log.push(op);
+            self.rebuild_modal_filters();
+        }
+    }
+
+    /// Unions another planner's operation log into ours, resolving conflicts
+    /// on the same road by highest `(counter, actor)` wins, and rebuilds the
+    /// derived modal filters from the merged log.
+    pub fn merge(&mut self, other_ops: Vec<Op>) {
+        for op in other_ops {
+            if !self
+                .op_log
+                .iter()
+                .any(|o| o.actor == op.actor && o.counter == op.counter)
+            {
+                self.op_log.push(op);
+            }
+        }
+        self.rebuild_modal_filters();
+    }
+
+    /// An opaque hash of the current op log, usable as a "heads" token to
+    /// detect whether two savefiles have diverged.
+    pub fn commit(&self) -> String {
+        let mut ops: Vec<&Op> = self.op_log.iter().collect();
+        ops.sort_by_key(|o| (o.road, o.counter, o.actor.clone()));
+
+        // `std::collections::hash_map::DefaultHasher`'s output isn't
+        // contractually stable across std/compiler versions, which would
+        // break this as a cross-planner divergence check -- two planners on
+        // different toolchains could get different heads for the same op
+        // log. FNV-1a over the fields' own bytes is stable by construction.
+        let mut hash = fnv1a(&[]);
+        for op in ops {
+            hash = fnv1a_continue(hash, op.actor.as_bytes());
+            hash = fnv1a_continue(hash, &op.counter.to_le_bytes());
+            hash = fnv1a_continue(hash, &op.road.0.to_le_bytes());
+            match &op.kind {
+                OpKind::AddFilter(kind) => hash = fnv1a_continue(hash, kind.to_string().as_bytes()),
+                OpKind::DeleteFilter => hash = fnv1a_continue(hash, b"delete"),
+            }
+        }
+        format!("{hash:016x}")
+    }
+
+    /// Rebuilds `modal_filters` from the op log: for each road, the winning
+    /// op is whichever has the highest `(counter, actor)`, and it's either an
+    /// add (insert) or a delete (absent).
+    fn rebuild_modal_filters(&mut self) {
+        let mut winners: HashMap<RoadID, &Op> = HashMap::new();
+        for op in &self.op_log {
+            match winners.get(&op.road) {
+                Some(existing)
+                    if (existing.counter, &existing.actor) >= (op.counter, &op.actor) => {}
+                _ => {
+                    winners.insert(op.road, op);
+                }
+            }
+        }
+
+        self.modal_filters.clear();
+        self.deleted_filters.clear();
+        for (road, op) in winners {
+            match &op.kind {
+                OpKind::AddFilter(kind) => {
+                    self.modal_filters.insert(
+                        road,
+                        ModalFilter {
+                            kind: *kind,
+                            percent_along: 0.5,
+                        },
+                    );
+                }
+                OpKind::DeleteFilter => {
+                    self.deleted_filters.insert(road);
+                }
+            }
+        }
+    }
+
+    /// GJ with the boundary (if any) and a feature per CRDT op, so the result
+    /// can be fed into `load_savefile` or `merge_savefile` elsewhere and reproduce the
+    /// exact same edit history, not just its final state.
+    pub fn to_savefile(&self, neighbourhood: Option<&Neighbourhood>) -> geojson::FeatureCollection {
+        let mut features = Vec::new();
+        for op in &self.op_log {
+            let road = self.get_r(op.road);
+            let mut f = road.to_gj(&self.mercator);
+            f.set_property("kind", "op");
+            f.set_property("actor", op.actor.clone());
+            f.set_property("counter", op.counter);
+            match &op.kind {
+                OpKind::AddFilter(kind) => f.set_property("op", format!("add:{}", kind.to_string())),
+                OpKind::DeleteFilter => f.set_property("op", "delete"),
+            }
+            features.push(f);
+        }
+        if let Some(n) = neighbourhood {
+            let mut f = Feature::from(Geometry::from(
+                &self.mercator.to_wgs84(&n.boundary_polygon),
+            ));
+            f.set_property("kind", "boundary");
+            features.push(f);
+        }
+        geojson::FeatureCollection {
+            bbox: None,
+            features,
+            foreign_members: None,
+        }
+    }
+
+    fn parse_op(f: &Feature) -> Result<Op> {
+        let road = f
+            .property("id")
+            .and_then(|x| x.as_u64())
+            .ok_or_else(|| anyhow!("op feature missing id"))?;
+        let actor = f
+            .property("actor")
+            .and_then(|x| x.as_str())
+            .ok_or_else(|| anyhow!("op feature missing actor"))?
+            .to_string();
+        let counter = f
+            .property("counter")
+            .and_then(|x| x.as_u64())
+            .ok_or_else(|| anyhow!("op feature missing counter"))?;
+        let kind = match f.property("op").and_then(|x| x.as_str()) {
+            Some("delete") => OpKind::DeleteFilter,
+            Some(add) if add.starts_with("add:") => OpKind::AddFilter(
+                FilterKind::from_string(&add[4..])
+                    .ok_or_else(|| anyhow!("unrecognized filter kind in op: {add}"))?,
+            ),
+            other => bail!("unrecognized op: {other:?}"),
+        };
+        Ok(Op {
+            actor,
+            counter,
+            road: RoadID(road as usize),
+            kind,
+        })
+    }
+
+    /// Fully overwrites the current op log and modal filters with what's in
+    /// `gj`. Use `merge` instead to combine two planners' edit histories.
+    pub fn load_savefile(&mut self, gj: geojson::FeatureCollection) -> Result<Option<Polygon>> {
+        self.op_log.clear();
+        self.undo_buffer.clear();
+
+        let mut boundary = None;
+        let mut max_counter = None;
+        for f in gj.features {
+            match f.property("kind").and_then(|x| x.as_str()) {
+                Some("boundary") => {
+                    let mut polygon: Polygon = f.try_into()?;
+                    self.mercator.to_mercator_in_place(&mut polygon);
+                    boundary = Some(polygon);
+                }
+                Some("op") => {
+                    let op = Self::parse_op(&f)?;
+                    if op.actor == self.actor {
+                        max_counter = Some(max_counter.unwrap_or(0).max(op.counter + 1));
+                    }
+                    self.op_log.push(op);
+                }
+                _ => {}
+            }
+        }
+        if let Some(c) = max_counter {
+            self.next_counter = self.next_counter.max(c);
+        }
+        self.rebuild_modal_filters();
+        Ok(boundary)
+    }
+
+    /// Unions another savefile's op log into ours -- the two-file version of
+    /// `merge` -- and returns the new combined heads from `commit()`.
+    pub fn merge_savefile(&mut self, gj: geojson::FeatureCollection) -> Result<String> {
+        let mut incoming = Vec::new();
+        for f in gj.features {
+            if f.property("kind").and_then(|x| x.as_str()) == Some("op") {
+                incoming.push(Self::parse_op(&f)?);
+            }
+        }
+        self.merge(incoming);
+        Ok(self.commit())
+    }
+
+    pub fn compare_route(
+        &self,
+        pt1: Coord,
+        pt2: Coord,
+        profile: crate::route::Profile,
+        want_polyline: bool,
+    ) -> crate::route::RouteComparison {
+        let before = self
+            .router
+            .route(pt1, pt2, &self.existing_filters, profile)
+            .map(|r| r.to_result(&self.mercator, want_polyline));
+        let after = self
+            .router
+            .route(pt1, pt2, &self.effective_filters(), profile)
+            .map(|r| r.to_result(&self.mercator, want_polyline));
+        crate::route::RouteComparison { before, after }
+    }
+}
+
+/// A fresh random id identifying this planner's edits, distinct from every
+/// other planner who might edit the same neighbourhood concurrently.
+fn new_actor_id() -> String {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    (0..16).map(|_| format!("{:x}", rng.gen_range(0..16))).collect()
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// FNV-1a over an initial byte string, for starting a `commit()` hash. Unlike
+/// `DefaultHasher`, this is a fixed, documented algorithm whose output is
+/// stable across Rust versions and platforms.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    fnv1a_continue(FNV_OFFSET_BASIS, bytes)
+}
+
+/// Folds more bytes into an in-progress FNV-1a hash.
+fn fnv1a_continue(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &b in bytes {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
@@ -0,0 +1,154 @@
+use anyhow::Result;
+use geo::{Contains, Polygon};
+use geojson::GeoJson;
+
+use crate::map_model::{Intersection, IntersectionID, MapModel, Road, RoadID};
+
+/// A neighbourhood is a user-drawn boundary polygon, plus the roads and
+/// intersections derived from it: everything strictly inside is "interior",
+/// and the intersections straddling the boundary are the entry/exit points
+/// that through-traffic (and rat-runs) must use.
+pub struct Neighbourhood {
+    pub boundary_polygon: Polygon,
+    pub interior_roads: Vec<RoadID>,
+    pub border_intersections: Vec<IntersectionID>,
+}
+
+impl Neighbourhood {
+    pub fn new(map: &MapModel, boundary_polygon: Polygon) -> Result<Self> {
+        let (interior_roads, border_intersections) =
+            classify(&map.roads, &map.intersections, &boundary_polygon);
+
+        Ok(Self {
+            boundary_polygon,
+            interior_roads,
+            border_intersections,
+        })
+    }
+
+    pub fn to_gj(&self, map: &MapModel) -> GeoJson {
+        // `existing_filters` alone doesn't account for a planner deleting an
+        // imported filter outright, so go through `effective_filters` for
+        // the "still physically/currently there" half of the check below.
+        let effective = map.effective_filters();
+
+        let mut features = Vec::new();
+        for r in &self.interior_roads {
+            let mut f = map.get_r(*r).to_gj(&map.mercator);
+            // A proposed filter takes precedence over an existing one on the
+            // same road -- it's the planner's call to remove or replace it.
+            if let Some(filter) = map.modal_filters.get(r) {
+                f.set_property("filterKind", filter.kind.to_string());
+                f.set_property("existing", false);
+            } else if let Some(filter) = effective.get(r) {
+                f.set_property("filterKind", filter.kind.to_string());
+                f.set_property("existing", true);
+            }
+            features.push(f);
+        }
+        GeoJson::from(features)
+    }
+}
+
+/// A road is interior only if *both* endpoints are strictly inside the
+/// boundary -- a road with just one endpoint inside crosses the boundary and
+/// is how through-traffic enters/exits, not part of the interior. An
+/// intersection is a border (entry/exit) point if it's inside the boundary
+/// but touches at least one non-interior (boundary-crossing) road.
+fn classify(
+    roads: &[Road],
+    intersections: &[Intersection],
+    boundary_polygon: &Polygon,
+) -> (Vec<RoadID>, Vec<IntersectionID>) {
+    let mut interior_roads = Vec::new();
+    for road in roads {
+        let src_in = boundary_polygon.contains(&intersections[road.src_i.0].point);
+        let dst_in = boundary_polygon.contains(&intersections[road.dst_i.0].point);
+        if src_in && dst_in {
+            interior_roads.push(road.id);
+        }
+    }
+
+    let mut border_intersections = Vec::new();
+    for i in intersections {
+        let inside = boundary_polygon.contains(&i.point);
+        let on_boundary = i.roads.iter().any(|r| !interior_roads.contains(r));
+        if inside && on_boundary {
+            border_intersections.push(i.id);
+        }
+    }
+
+    (interior_roads, border_intersections)
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::{Coord, LineString};
+
+    use super::*;
+    use crate::common::Tags;
+
+    fn road(id: usize, src: usize, dst: usize, src_pt: Coord, dst_pt: Coord) -> Road {
+        Road {
+            id: RoadID(id),
+            src_i: IntersectionID(src),
+            dst_i: IntersectionID(dst),
+            linestring: LineString::new(vec![src_pt, dst_pt]),
+            tags: Tags::new(),
+            access_forward: true,
+            access_backward: true,
+        }
+    }
+
+    // A 3-node line 0 -- 1 -- 2, with a boundary that cuts through the
+    // second road (1 -- 2). Intersection 1 should be detected as a border
+    // intersection, and only road 0 (0 -- 1) should be interior.
+    #[test]
+    fn boundary_cutting_through_a_road_yields_a_border_intersection() {
+        let pts = [
+            Coord { x: 0.0, y: 0.0 },
+            Coord { x: 10.0, y: 0.0 },
+            Coord { x: 20.0, y: 0.0 },
+        ];
+        let roads = vec![
+            road(0, 0, 1, pts[0], pts[1]),
+            road(1, 1, 2, pts[1], pts[2]),
+        ];
+        let intersections = vec![
+            Intersection {
+                id: IntersectionID(0),
+                point: pts[0],
+                roads: vec![RoadID(0)],
+            },
+            Intersection {
+                id: IntersectionID(1),
+                point: pts[1],
+                roads: vec![RoadID(0), RoadID(1)],
+            },
+            Intersection {
+                id: IntersectionID(2),
+                point: pts[2],
+                roads: vec![RoadID(1)],
+            },
+        ];
+        // Covers everything with x < 15, so intersection 2 (x=20) is
+        // outside and road 1 (1 -- 2) straddles the boundary.
+        let boundary_polygon = Polygon::new(
+            LineString::new(vec![
+                Coord { x: -5.0, y: -5.0 },
+                Coord { x: 15.0, y: -5.0 },
+                Coord { x: 15.0, y: 5.0 },
+                Coord { x: -5.0, y: 5.0 },
+                Coord { x: -5.0, y: -5.0 },
+            ]),
+            vec![],
+        );
+
+        let (interior_roads, border_intersections) =
+            classify(&roads, &intersections, &boundary_polygon);
+
+        assert_eq!(interior_roads, vec![RoadID(0)]);
+        assert!(!border_intersections.is_empty());
+        assert_eq!(border_intersections, vec![IntersectionID(1)]);
+    }
+}
@@ -0,0 +1,6 @@
+use crate::cells::Cell;
+
+/// Wraps computed `Cell`s for rendering as a colored GeoJSON layer in the UI.
+pub struct RenderCells {
+    pub cells: Vec<Cell>,
+}
@@ -0,0 +1,281 @@
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use geo::{Coord, EuclideanDistance, LineString};
+use geojson::{Feature, Geometry};
+use serde::Serialize;
+use utils::Mercator;
+
+use crate::common::encode_polyline;
+use crate::map_model::{Intersection, IntersectionID, ModalFilter, Restriction, Road, RoadID};
+
+/// Which mode of travel a route is being compared for. Each has its own cost
+/// model and access rules -- notably, only `Car` is stopped by modal filters
+/// or one-ways, since letting bikes and pedestrians through is the point of
+/// an LTN.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Profile {
+    Car,
+    Bicycle,
+    Foot,
+}
+
+impl Profile {
+    pub fn from_string(x: &str) -> Option<Self> {
+        match x {
+            "car" => Some(Self::Car),
+            "bicycle" => Some(Self::Bicycle),
+            "foot" => Some(Self::Foot),
+            _ => None,
+        }
+    }
+
+    /// Ignores modal filters, one-ways, and turn restrictions -- all of which
+    /// only apply to motor traffic.
+    fn ignores_restrictions(self) -> bool {
+        self != Self::Car
+    }
+
+    /// A reasonable constant cruising speed in meters/second, for estimating
+    /// travel time. Real speed limits and gradient aren't modeled.
+    fn speed_mps(self) -> f64 {
+        match self {
+            Self::Car => 8.9,     // ~20mph, a typical urban/residential limit
+            Self::Bicycle => 4.5, // ~10mph
+            Self::Foot => 1.4,    // ~3mph
+        }
+    }
+}
+
+/// A simple Dijkstra router over the road network, used for shortcut detection
+/// and for comparing routes before/after modal filters are applied.
+pub struct Router {
+    // Directed adjacency, respecting one-ways/`access=no`: used for `Car`.
+    edges_directed: HashMap<IntersectionID, Vec<(RoadID, IntersectionID, f64)>>,
+    // Every road, traversable both ways: used for `Bicycle`/`Foot`.
+    edges_undirected: HashMap<IntersectionID, Vec<(RoadID, IntersectionID, f64)>>,
+    geometry: HashMap<RoadID, LineString>,
+    intersection_points: HashMap<IntersectionID, Coord>,
+    banned_turns: HashSet<(RoadID, IntersectionID, RoadID)>,
+}
+
+pub struct RoutePath {
+    pub roads: Vec<RoadID>,
+    pub geometry: LineString,
+    pub distance_meters: f64,
+    pub time_seconds: f64,
+}
+
+impl RoutePath {
+    pub fn to_gj(&self, mercator: &Mercator, label: &'static str) -> Feature {
+        let mut f = Feature::from(Geometry::from(&mercator.to_wgs84(&self.geometry)));
+        f.set_property("kind", label);
+        f.set_property("distance_meters", self.distance_meters);
+        f.set_property("time_seconds", self.time_seconds);
+        f
+    }
+
+    pub fn to_polyline(&self, mercator: &Mercator) -> String {
+        encode_polyline(&mercator.to_wgs84(&self.geometry).0)
+    }
+}
+
+// Dijkstra state. Tracks the road we arrived on (if any) so turn
+// restrictions -- which apply to a (from, via, to) triple, not just a node --
+// can be checked when considering the next hop.
+#[derive(PartialEq)]
+struct State {
+    cost: f64,
+    intersection: IntersectionID,
+    arrived_via: Option<RoadID>,
+}
+impl Eq for State {}
+impl Ord for State {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        other.cost.partial_cmp(&self.cost).unwrap()
+    }
+}
+impl PartialOrd for State {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Router {
+    pub fn new(roads: &[Road], intersections: &[Intersection], restrictions: &[Restriction]) -> Self {
+        let mut edges_directed: HashMap<IntersectionID, Vec<(RoadID, IntersectionID, f64)>> =
+            HashMap::new();
+        let mut edges_undirected: HashMap<IntersectionID, Vec<(RoadID, IntersectionID, f64)>> =
+            HashMap::new();
+        let mut geometry = HashMap::new();
+        for r in roads {
+            let len = r.length_meters();
+            if r.access_forward {
+                edges_directed
+                    .entry(r.src_i)
+                    .or_default()
+                    .push((r.id, r.dst_i, len));
+            }
+            if r.access_backward {
+                edges_directed
+                    .entry(r.dst_i)
+                    .or_default()
+                    .push((r.id, r.src_i, len));
+            }
+            edges_undirected
+                .entry(r.src_i)
+                .or_default()
+                .push((r.id, r.dst_i, len));
+            edges_undirected
+                .entry(r.dst_i)
+                .or_default()
+                .push((r.id, r.src_i, len));
+            geometry.insert(r.id, r.linestring.clone());
+        }
+        let intersection_points = intersections.iter().map(|i| (i.id, i.point)).collect();
+        let banned_turns = restrictions.iter().map(|r| (r.from, r.via, r.to)).collect();
+        Self {
+            edges_directed,
+            edges_undirected,
+            geometry,
+            intersection_points,
+            banned_turns,
+        }
+    }
+
+    /// Dijkstra's from one intersection to another, for the given profile.
+    /// For `Car`, skips any road with a modal filter or a banned turn.
+    pub fn route_between(
+        &self,
+        from: IntersectionID,
+        to: IntersectionID,
+        modal_filters: &HashMap<RoadID, ModalFilter>,
+        profile: Profile,
+    ) -> Option<(f64, Vec<RoadID>)> {
+        let edges = if profile.ignores_restrictions() {
+            &self.edges_undirected
+        } else {
+            &self.edges_directed
+        };
+
+        let mut dist: HashMap<(IntersectionID, Option<RoadID>), f64> = HashMap::new();
+        let mut prev: HashMap<(IntersectionID, Option<RoadID>), (IntersectionID, Option<RoadID>, RoadID)> =
+            HashMap::new();
+        let mut queue = BinaryHeap::new();
+
+        dist.insert((from, None), 0.0);
+        queue.push(State {
+            cost: 0.0,
+            intersection: from,
+            arrived_via: None,
+        });
+
+        while let Some(State {
+            cost,
+            intersection,
+            arrived_via,
+        }) = queue.pop()
+        {
+            let key = (intersection, arrived_via);
+            if cost > *dist.get(&key).unwrap_or(&f64::INFINITY) {
+                continue;
+            }
+            if intersection == to {
+                let mut roads = Vec::new();
+                let mut cur = key;
+                while let Some((prev_i, prev_via, r)) = prev.get(&cur) {
+                    roads.push(*r);
+                    cur = (*prev_i, *prev_via);
+                }
+                roads.reverse();
+                return Some((cost, roads));
+            }
+            for (road, next_i, len) in edges.get(&intersection).into_iter().flatten() {
+                if !profile.ignores_restrictions() {
+                    if modal_filters.contains_key(road) {
+                        continue;
+                    }
+                    if let Some(via_road) = arrived_via {
+                        if self.banned_turns.contains(&(via_road, intersection, *road)) {
+                            continue;
+                        }
+                    }
+                }
+                let next_key = (*next_i, Some(*road));
+                let next_cost = cost + len;
+                if next_cost < *dist.get(&next_key).unwrap_or(&f64::INFINITY) {
+                    dist.insert(next_key, next_cost);
+                    prev.insert(next_key, (intersection, arrived_via, *road));
+                    queue.push(State {
+                        cost: next_cost,
+                        intersection: *next_i,
+                        arrived_via: Some(*road),
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    fn nearest_intersection(&self, pt: Coord) -> Option<IntersectionID> {
+        self.intersection_points
+            .iter()
+            .min_by(|(_, a), (_, b)| {
+                a.euclidean_distance(&pt)
+                    .partial_cmp(&b.euclidean_distance(&pt))
+                    .unwrap()
+            })
+            .map(|(i, _)| *i)
+    }
+
+    /// Point-to-point routing, snapping to the nearest intersection on each end.
+    pub fn route(
+        &self,
+        pt1: Coord,
+        pt2: Coord,
+        modal_filters: &HashMap<RoadID, ModalFilter>,
+        profile: Profile,
+    ) -> Option<RoutePath> {
+        let from = self.nearest_intersection(pt1)?;
+        let to = self.nearest_intersection(pt2)?;
+        let (distance_meters, roads) = self.route_between(from, to, modal_filters, profile)?;
+        let mut pts = Vec::new();
+        for r in &roads {
+            pts.extend(self.geometry[r].0.clone());
+        }
+        Some(RoutePath {
+            roads,
+            geometry: LineString::new(pts),
+            distance_meters,
+            time_seconds: distance_meters / profile.speed_mps(),
+        })
+    }
+}
+
+/// One profile's before/after route comparison, ready to serialize to JS.
+#[derive(Serialize)]
+pub struct RouteComparison {
+    pub before: Option<RouteResult>,
+    pub after: Option<RouteResult>,
+}
+
+#[derive(Serialize)]
+pub struct RouteResult {
+    pub distance_meters: f64,
+    pub time_seconds: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geometry: Option<geojson::Geometry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub polyline: Option<String>,
+}
+
+impl RoutePath {
+    pub fn to_result(&self, mercator: &Mercator, want_polyline: bool) -> RouteResult {
+        RouteResult {
+            distance_meters: self.distance_meters,
+            time_seconds: self.time_seconds,
+            polyline: want_polyline.then(|| self.to_polyline(mercator)),
+            geometry: (!want_polyline)
+                .then(|| Geometry::from(&mercator.to_wgs84(&self.geometry))),
+        }
+    }
+}
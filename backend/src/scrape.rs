@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use geo::{LineString, Polygon};
+use utils::Mercator;
+
+use crate::common::barrier_to_filter_kind;
+use crate::map_model::{Intersection, IntersectionID, ModalFilter, Restriction, Road, RoadID};
+
+pub struct Scraped {
+    pub mercator: Mercator,
+    pub boundary_polygon: Polygon,
+    pub roads: Vec<Road>,
+    pub intersections: Vec<Intersection>,
+    /// Modal filters that physically already exist, imported from OSM barrier
+    /// nodes sitting on any scraped road. This isn't limited to a
+    /// neighbourhood's interior roads; no neighbourhood exists at this point.
+    pub existing_filters: HashMap<RoadID, ModalFilter>,
+    pub turn_restrictions: Vec<Restriction>,
+}
+
+/// Parses an osm.pbf or osm.xml byte string into the road graph that
+/// `MapModel` operates on, including interventions already tagged in OSM:
+/// point barriers become pre-existing modal filters, `oneway`/`access` tags
+/// make the graph directed, and `type=restriction` relations become banned
+/// turns.
+pub fn scrape(input_bytes: &[u8]) -> Result<Scraped> {
+    let mut osm_reader = utils::osm2graph::Graph::new(input_bytes, |_tags| true)?;
+    let mercator = osm_reader.mercator.clone();
+    let boundary_polygon = mercator.to_mercator(&osm_reader.boundary_polygon);
+
+    let mut intersections = Vec::new();
+    for (idx, node) in osm_reader.intersections.drain(..).enumerate() {
+        intersections.push(Intersection {
+            id: IntersectionID(idx),
+            point: node.point,
+            roads: node.roads.into_iter().map(RoadID).collect(),
+        });
+    }
+
+    let mut roads = Vec::new();
+    for (idx, edge) in osm_reader.edges.drain(..).enumerate() {
+        let (access_forward, access_backward) = access_from_tags(&edge.osm_tags);
+        roads.push(Road {
+            id: RoadID(idx),
+            src_i: IntersectionID(edge.src),
+            dst_i: IntersectionID(edge.dst),
+            linestring: LineString::new(edge.points),
+            tags: edge.osm_tags,
+            access_forward,
+            access_backward,
+        });
+    }
+
+    let existing_filters = scrape_barriers(&osm_reader.barrier_nodes, &roads);
+    let turn_restrictions = scrape_turn_restrictions(&osm_reader.restrictions, &roads);
+
+    Ok(Scraped {
+        mercator,
+        boundary_polygon,
+        roads,
+        intersections,
+        existing_filters,
+        turn_restrictions,
+    })
+}
+
+/// `oneway=yes|true|1` allows only src->dst; `oneway=-1|reverse` allows only dst->src;
+/// `access`/`motor_vehicle=no` blocks motor traffic in both directions.
+/// Everything else is assumed two-way.
+fn access_from_tags(tags: &crate::common::Tags) -> (bool, bool) {
+    if matches!(tags.get("access").map(String::as_str), Some("no"))
+        || matches!(tags.get("motor_vehicle").map(String::as_str), Some("no"))
+    {
+        return (false, false);
+    }
+    match tags.get("oneway").map(String::as_str) {
+        Some("yes") | Some("true") | Some("1") => (true, false),
+        Some("-1") | Some("reverse") => (false, true),
+        _ => (true, true),
+    }
+}
+
+/// Point barrier nodes (`barrier=bollard|gate|block|lift_gate`) that sit on
+/// a scraped road become pre-existing modal filters on that road.
+fn scrape_barriers(
+    barrier_nodes: &[utils::osm2graph::BarrierNode],
+    roads: &[Road],
+) -> HashMap<RoadID, ModalFilter> {
+    let mut out = HashMap::new();
+    for barrier in barrier_nodes {
+        let Some(kind) = barrier_to_filter_kind(&barrier.barrier) else {
+            continue;
+        };
+        // `utils::osm2graph` only sets `edge_idx` for a barrier node that
+        // actually splits a road mid-way; one that falls on a junction node
+        // isn't attached to any single edge and is skipped here.
+        let Some(road_idx) = barrier.edge_idx else {
+            continue;
+        };
+        let road = &roads[road_idx];
+        out.insert(
+            road.id,
+            ModalFilter {
+                kind,
+                percent_along: barrier.percent_along,
+            },
+        );
+    }
+    out
+}
+
+/// `type=restriction` relations with `restriction=no_*` become banned turns.
+/// `only_*` restrictions aren't modeled yet -- they'd require forbidding
+/// every turn except the one named, which needs enumerating the via node's
+/// other roads.
+fn scrape_turn_restrictions(
+    restrictions: &[utils::osm2graph::RestrictionRelation],
+    roads: &[Road],
+) -> Vec<Restriction> {
+    let mut out = Vec::new();
+    for rel in restrictions {
+        if !rel.restriction.starts_with("no_") {
+            continue;
+        }
+        let (Some(from_idx), Some(to_idx)) = (rel.from_edge_idx, rel.to_edge_idx) else {
+            continue;
+        };
+        let from = roads[from_idx].id;
+        let to = roads[to_idx].id;
+        out.push(Restriction {
+            from,
+            via: IntersectionID(rel.via_node_idx),
+            to,
+        });
+    }
+    out
+}
@@ -0,0 +1,250 @@
+use std::collections::{HashMap, HashSet};
+
+use geojson::{Feature, FeatureCollection, GeoJson, Geometry};
+
+use crate::map_model::{IntersectionID, MapModel, ModalFilter, Road, RoadID};
+use crate::neighbourhood::Neighbourhood;
+
+/// A single origin->destination path that crosses through a neighbourhood.
+pub struct Path {
+    pub roads: Vec<RoadID>,
+}
+
+impl Path {
+    pub fn geometry(&self, map: &MapModel) -> geo::LineString {
+        let mut pts = Vec::new();
+        for r in &self.roads {
+            pts.extend(map.get_r(*r).linestring.0.clone());
+        }
+        geo::LineString::new(pts)
+    }
+}
+
+/// All rat-run paths crossing a neighbourhood's interior roads, found by
+/// routing between every pair of border (entry/exit) intersections.
+pub struct Shortcuts {
+    paths: Vec<Path>,
+}
+
+impl Shortcuts {
+    pub fn new(map: &MapModel, n: &Neighbourhood) -> Self {
+        let mut paths = Vec::new();
+        let border = &n.border_intersections;
+        for (idx, from) in border.iter().enumerate() {
+            for to in &border[idx + 1..] {
+                if let Some((_, roads)) =
+                    map.router
+                        .route_between(*from, *to, &map.modal_filters, crate::route::Profile::Car)
+                {
+                    // Only a "shortcut" if it actually threads through the interior,
+                    // not just touches one entry and immediately leaves again.
+                    if roads.iter().any(|r| n.interior_roads.contains(r)) {
+                        paths.push(Path { roads });
+                    }
+                }
+            }
+        }
+        Self { paths }
+    }
+
+    /// All paths that cross the given road.
+    pub fn subset(&self, road: RoadID) -> Vec<&Path> {
+        self.paths
+            .iter()
+            .filter(|p| p.roads.contains(&road))
+            .collect()
+    }
+}
+
+/// If motor traffic can drive along `road` starting at `from`, returns the
+/// intersection it arrives at. Mirrors `Router`'s directed adjacency, so this
+/// DFS and `Shortcuts::new`'s Dijkstra agree about which way the network can
+/// actually be driven.
+fn drive_along(road: &Road, from: IntersectionID) -> Option<IntersectionID> {
+    if road.src_i == from && road.access_forward {
+        Some(road.dst_i)
+    } else if road.dst_i == from && road.access_backward {
+        Some(road.src_i)
+    } else {
+        None
+    }
+}
+
+/// Builds the direct edges for the all-pairs matrix: the driving distance --
+/// and the actual chain of interior roads used -- between each pair of
+/// border intersections that're connected by an unbroken, legally-driveable
+/// chain of interior roads, honoring the given modal filters, one-ways, and
+/// turn restrictions. Pairs with no direct interior connection are left
+/// absent (treated as infinite by the caller).
+fn direct_edges(
+    map: &MapModel,
+    n: &Neighbourhood,
+    modal_filters: &HashMap<RoadID, ModalFilter>,
+) -> HashMap<(IntersectionID, IntersectionID), (f64, Vec<RoadID>)> {
+    let border: HashSet<IntersectionID> = n.border_intersections.iter().copied().collect();
+    let interior: HashSet<RoadID> = n.interior_roads.iter().copied().collect();
+    let banned_turns: HashSet<(RoadID, IntersectionID, RoadID)> = map
+        .turn_restrictions
+        .iter()
+        .map(|r| (r.from, r.via, r.to))
+        .collect();
+
+    let mut edges: HashMap<(IntersectionID, IntersectionID), (f64, Vec<RoadID>)> = HashMap::new();
+    for &start in &n.border_intersections {
+        // DFS along interior roads, stopping whenever we hit another border
+        // node. `arrived_via` is the road just driven, so a turn restriction
+        // (which bans a specific from/via/to triple) can be checked before
+        // continuing through an intersection.
+        let mut stack = vec![(start, 0.0, None::<RoadID>, Vec::<RoadID>::new())];
+        let mut visited = HashSet::new();
+        while let Some((cur, dist_so_far, arrived_via, path)) = stack.pop() {
+            if !visited.insert((cur, arrived_via)) {
+                continue;
+            }
+            let intersection = map.get_i(cur);
+            for &r in &intersection.roads {
+                if !interior.contains(&r) || modal_filters.contains_key(&r) {
+                    continue;
+                }
+                if let Some(via_road) = arrived_via {
+                    if banned_turns.contains(&(via_road, cur, r)) {
+                        continue;
+                    }
+                }
+                let road = map.get_r(r);
+                let Some(next_i) = drive_along(road, cur) else {
+                    continue;
+                };
+                let next_dist = dist_so_far + road.length_meters();
+                let mut next_path = path.clone();
+                next_path.push(r);
+                if border.contains(&next_i) && next_i != start {
+                    let key = (start, next_i);
+                    let best = edges.get(&key).map(|(d, _)| *d).unwrap_or(f64::INFINITY);
+                    if next_dist < best {
+                        edges.insert(key, (next_dist, next_path));
+                    }
+                } else if !border.contains(&next_i) {
+                    stack.push((next_i, next_dist, Some(r), next_path));
+                }
+            }
+        }
+    }
+    edges
+}
+
+/// Runs Floyd-Warshall over a neighbourhood's entry/exit intersections, once
+/// with only the filters imported from OSM applied ("before") and once with
+/// every current filter applied ("after"), and returns a
+/// GeoJSON `FeatureCollection` coloring every interior road by how many
+/// origin->destination pairs route through it in each scenario.
+pub fn score_all_shortcuts(map: &MapModel, n: &Neighbourhood) -> GeoJson {
+    let before = score_one(map, n, &map.existing_filters);
+    let after = score_one(map, n, &map.effective_filters());
+
+    let mut features = Vec::new();
+    for &r in &n.interior_roads {
+        let mut f = map.get_r(r).to_gj(&map.mercator);
+        f.set_property("shortcuts_before", *before.get(&r).unwrap_or(&0));
+        f.set_property("shortcuts_after", *after.get(&r).unwrap_or(&0));
+        features.push(f);
+    }
+    GeoJson::from(FeatureCollection {
+        bbox: None,
+        features,
+        foreign_members: None,
+    })
+}
+
+fn score_one(
+    map: &MapModel,
+    n: &Neighbourhood,
+    modal_filters: &HashMap<RoadID, ModalFilter>,
+) -> HashMap<RoadID, usize> {
+    let nodes = &n.border_intersections;
+    let num = nodes.len();
+    let index: HashMap<IntersectionID, usize> =
+        nodes.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+
+    // Direct (i, j) node-index pairs to the actual road chain that edge
+    // weight came from -- the only roads `reconstruct_roads` is allowed to
+    // attribute a hop to, so attribution always matches the distances this
+    // same `modal_filters` scenario produced.
+    let mut edge_roads: HashMap<(usize, usize), Vec<RoadID>> = HashMap::new();
+
+    let mut dist = vec![vec![f64::INFINITY; num]; num];
+    let mut pred = vec![vec![usize::MAX; num]; num];
+    for i in 0..num {
+        dist[i][i] = 0.0;
+        pred[i][i] = i;
+    }
+    for ((a, b), (weight, roads)) in direct_edges(map, n, modal_filters) {
+        let i = index[&a];
+        let j = index[&b];
+        if weight < dist[i][j] {
+            dist[i][j] = weight;
+            pred[i][j] = i;
+            edge_roads.insert((i, j), roads);
+        }
+    }
+
+    // Floyd-Warshall. All weights are positive, so there's no need to guard
+    // against negative cycles.
+    for k in 0..num {
+        for i in 0..num {
+            if dist[i][k] == f64::INFINITY {
+                continue;
+            }
+            for j in 0..num {
+                let candidate = dist[i][k] + dist[k][j];
+                if candidate < dist[i][j] {
+                    dist[i][j] = candidate;
+                    pred[i][j] = pred[k][j];
+                }
+            }
+        }
+    }
+
+    let mut counts: HashMap<RoadID, usize> = HashMap::new();
+    for i in 0..num {
+        for j in 0..num {
+            if i == j || dist[i][j].is_infinite() {
+                continue;
+            }
+            for r in reconstruct_roads(&pred, &edge_roads, i, j) {
+                *counts.entry(r).or_insert(0) += 1;
+            }
+        }
+    }
+    counts
+}
+
+// Walks `pred` backwards from j to i, attributing each hop to the exact road
+// chain `direct_edges` found for it under this same filter scenario -- never
+// re-routing, which could disagree with the distances the matrix scored.
+fn reconstruct_roads(
+    pred: &[Vec<usize>],
+    edge_roads: &HashMap<(usize, usize), Vec<RoadID>>,
+    i: usize,
+    j: usize,
+) -> Vec<RoadID> {
+    let mut chain = vec![j];
+    let mut cur = j;
+    while cur != i {
+        let p = pred[i][cur];
+        if p == usize::MAX || p == cur {
+            break;
+        }
+        chain.push(p);
+        cur = p;
+    }
+    chain.reverse();
+
+    let mut roads = Vec::new();
+    for w in chain.windows(2) {
+        if let Some(hop_roads) = edge_roads.get(&(w[0], w[1])) {
+            roads.extend(hop_roads.iter().copied());
+        }
+    }
+    roads
+}
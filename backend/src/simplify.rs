@@ -0,0 +1,248 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::map_model::{Intersection, IntersectionID, ModalFilter, Restriction, Road, RoadID};
+
+/// OSM frequently splits a single street into many short `Road`s joined at
+/// two-way `Intersection`s that aren't real junctions. This collapses those
+/// degenerate intersections -- degree 2, same name and highway class, compatible
+/// direction -- merging the adjacent roads into one, and remaps every
+/// `RoadID`/`IntersectionID` reference (existing filters, turn restrictions)
+/// to match. Everything downstream -- `to_route_snapper`, `Cell`
+/// computation, `Shortcuts` -- then operates on the simplified graph.
+pub fn simplify(
+    roads: Vec<Road>,
+    intersections: Vec<Intersection>,
+    existing_filters: HashMap<RoadID, ModalFilter>,
+    turn_restrictions: Vec<Restriction>,
+) -> (
+    Vec<Road>,
+    Vec<Intersection>,
+    HashMap<RoadID, ModalFilter>,
+    Vec<Restriction>,
+) {
+    // Turn restrictions anchor to a real junction; don't merge through one.
+    let restricted_intersections: HashSet<IntersectionID> =
+        turn_restrictions.iter().map(|r| r.via).collect();
+
+    let mut roads: HashMap<RoadID, Road> = roads.into_iter().map(|r| (r.id, r)).collect();
+    let mut intersections: HashMap<IntersectionID, Intersection> =
+        intersections.into_iter().map(|i| (i.id, i)).collect();
+
+    // Maps an original RoadID to whatever RoadID now represents it, after
+    // zero or more merges.
+    let mut road_remap: HashMap<RoadID, RoadID> =
+        roads.keys().map(|id| (*id, *id)).collect();
+
+    // Each merge updates the surviving endpoints' road lists in place, so a
+    // chain of degenerate intersections collapses within one pass; keep
+    // sweeping anyway until a full pass changes nothing.
+    loop {
+        let mut candidates: Vec<IntersectionID> = intersections.keys().copied().collect();
+        candidates.sort();
+        let mut merged_any = false;
+
+        for i in candidates {
+            if restricted_intersections.contains(&i) {
+                continue;
+            }
+            let Some(intersection) = intersections.get(&i) else {
+                continue;
+            };
+            if intersection.roads.len() != 2 {
+                continue;
+            }
+            let (r1, r2) = (intersection.roads[0], intersection.roads[1]);
+            if r1 == r2 {
+                // A loop road touching the same intersection twice; not a
+                // through-junction we can collapse.
+                continue;
+            }
+            let (Some(road1), Some(road2)) = (roads.get(&r1), roads.get(&r2)) else {
+                continue;
+            };
+            if !mergeable(road1, road2, i) {
+                continue;
+            }
+
+            let road1 = roads.remove(&r1).unwrap();
+            let road2 = roads.remove(&r2).unwrap();
+            intersections.remove(&i);
+
+            let merged = concatenate(road1, road2, i);
+            let merged_id = merged.id;
+
+            // Whichever endpoint used to point at the removed road, now points at
+            // the merged one.
+            for end in [merged.src_i, merged.dst_i] {
+                if let Some(other) = intersections.get_mut(&end) {
+                    for r in &mut other.roads {
+                        if *r == r1 || *r == r2 {
+                            *r = merged_id;
+                        }
+                    }
+                    other.roads.sort();
+                    other.roads.dedup();
+                }
+            }
+
+            for target in road_remap.values_mut() {
+                if *target == r1 || *target == r2 {
+                    *target = merged_id;
+                }
+            }
+            roads.insert(merged_id, merged);
+            merged_any = true;
+        }
+
+        if !merged_any {
+            break;
+        }
+    }
+
+    // Renumber everything to a contiguous 0..n range.
+    let mut road_ids: Vec<RoadID> = roads.keys().copied().collect();
+    road_ids.sort();
+    let road_renumber: HashMap<RoadID, RoadID> = road_ids
+        .iter()
+        .enumerate()
+        .map(|(new_idx, old)| (*old, RoadID(new_idx)))
+        .collect();
+
+    let mut intersection_ids: Vec<IntersectionID> = intersections.keys().copied().collect();
+    intersection_ids.sort();
+    let intersection_renumber: HashMap<IntersectionID, IntersectionID> = intersection_ids
+        .iter()
+        .enumerate()
+        .map(|(new_idx, old)| (*old, IntersectionID(new_idx)))
+        .collect();
+
+    let final_roads: Vec<Road> = road_ids
+        .iter()
+        .map(|old| {
+            let mut r = roads.remove(old).unwrap();
+            r.id = road_renumber[old];
+            r.src_i = intersection_renumber[&r.src_i];
+            r.dst_i = intersection_renumber[&r.dst_i];
+            r
+        })
+        .collect();
+
+    let final_intersections: Vec<Intersection> = intersection_ids
+        .iter()
+        .map(|old| {
+            let mut i = intersections.remove(old).unwrap();
+            i.id = intersection_renumber[old];
+            i.roads = i
+                .roads
+                .iter()
+                .map(|r| road_renumber[&road_remap[r]])
+                .collect();
+            i.roads.dedup();
+            i
+        })
+        .collect();
+
+    let final_filters = existing_filters
+        .into_iter()
+        .filter_map(|(r, filter)| road_renumber.get(&road_remap[&r]).map(|id| (*id, filter)))
+        .collect();
+
+    let final_restrictions = turn_restrictions
+        .into_iter()
+        .filter_map(|r| {
+            Some(Restriction {
+                from: *road_renumber.get(&road_remap[&r.from])?,
+                via: *intersection_renumber.get(&r.via)?,
+                to: *road_renumber.get(&road_remap[&r.to])?,
+            })
+        })
+        .collect();
+
+    (final_roads, final_intersections, final_filters, final_restrictions)
+}
+
+/// Two roads meeting at a degree-2 intersection can be merged if they share
+/// the same name and highway class, and merging wouldn't create an
+/// inconsistent direction (e.g. two one-ways both pointing away from `at`).
+fn mergeable(road1: &Road, road2: &Road, at: IntersectionID) -> bool {
+    if road1.tags.get("name") != road2.tags.get("name")
+        || road1.tags.get("highway") != road2.tags.get("highway")
+    {
+        return false;
+    }
+
+    let both_two_way = road1.access_forward
+        && road1.access_backward
+        && road2.access_forward
+        && road2.access_backward;
+    if both_two_way {
+        return true;
+    }
+
+    // Otherwise, both must be one-way and flow consistently through `at`:
+    // one arrives at `at`, the other leaves it, in the same net direction.
+    if road1.access_forward == road1.access_backward || road2.access_forward == road2.access_backward
+    {
+        // One of them is still two-way despite not both being two-way above,
+        // or is closed to motor traffic both ways -- a two-way joined to a
+        // one-way can't be merged without either losing the restriction or
+        // misrepresenting the two-way segment, and a closed road isn't merged.
+        return false;
+    }
+    let road1_flows_into_at = (road1.dst_i == at && road1.access_forward)
+        || (road1.src_i == at && road1.access_backward);
+    let road2_flows_out_of_at = (road2.src_i == at && road2.access_forward)
+        || (road2.dst_i == at && road2.access_backward);
+    road1_flows_into_at == road2_flows_out_of_at
+}
+
+/// Concatenates two roads meeting at `at` into one, unioning their tags and
+/// keeping whichever access flags correspond to the new combined direction.
+fn concatenate(road1: Road, road2: Road, at: IntersectionID) -> Road {
+    let (far1, rev1) = if road1.src_i == at {
+        (road1.dst_i, true)
+    } else {
+        (road1.src_i, false)
+    };
+    let (far2, rev2) = if road2.dst_i == at {
+        (road2.src_i, true)
+    } else {
+        (road2.dst_i, false)
+    };
+
+    let mut pts1 = road1.linestring.0.clone();
+    if rev1 {
+        pts1.reverse();
+    }
+    let mut pts2 = road2.linestring.0.clone();
+    if rev2 {
+        pts2.reverse();
+    }
+    // pts1 runs far1 -> at, pts2 runs at -> far2; the shared point at `at`
+    // would otherwise be duplicated.
+    pts1.pop();
+    pts1.extend(pts2);
+
+    // Re-derive access relative to the merged far1->far2 orientation by
+    // flipping each road's own src->dst flags through its rev: reversing a
+    // segment's geometry also reverses which of its flags means "forward".
+    let seg1_forward = if rev1 { road1.access_backward } else { road1.access_forward };
+    let seg1_backward = if rev1 { road1.access_forward } else { road1.access_backward };
+    let seg2_forward = if rev2 { road2.access_backward } else { road2.access_forward };
+    let seg2_backward = if rev2 { road2.access_forward } else { road2.access_backward };
+
+    let mut tags = road1.tags.clone();
+    tags.extend(road2.tags);
+
+    Road {
+        id: road1.id,
+        src_i: far1,
+        dst_i: far2,
+        linestring: geo::LineString::new(pts1),
+        tags,
+        // far1 -> far2 needs both segments passable in that direction.
+        access_forward: seg1_forward && seg2_forward,
+        // far2 -> far1 needs both segments passable the other way.
+        access_backward: seg2_backward && seg1_backward,
+    }
+}